@@ -7,20 +7,132 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
-use super::{AuthorisationKind, CmdError, DataAuthKind, Error, QueryResponse};
+use super::{
+    AuthorisationKind, CmdError, DataAuthKind, Error, NodeAuthKind, OperationId, QueryResponse,
+};
 use serde::{Deserialize, Serialize};
 use sn_data_types::{
-    PublicKey, Register, RegisterAddress as Address, RegisterEntry as Entry,
-    RegisterIndex as Index, RegisterOp, RegisterUser as User,
+    EntryHash, PublicKey, Register, RegisterAddress as Address, RegisterEntry as Entry,
+    RegisterIndex as Index, RegisterOp, RegisterPermission, RegisterUser as User, Signature,
 };
+use std::collections::BTreeSet;
 use std::fmt;
 use xor_name::XorName;
 
+/// A signed, scoped capability permit that lets a register owner delegate
+/// read/write access to their Register(s) without handing out their keys.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Debug, Serialize, Deserialize)]
+pub struct RegisterPermit {
+    /// Addresses this permit grants access to.
+    pub allowed_addresses: Vec<Address>,
+    /// Permissions granted by this permit.
+    pub permissions: Vec<RegisterPermission>,
+    /// Human-readable name for this permit, e.g. for display/audit purposes.
+    pub permit_name: String,
+    /// Identifier of the chain/context this permit is valid within.
+    pub chain_id: String,
+    /// Signature binding this permit to the owner that granted it.
+    pub permit_signature: PermitSignature,
+}
+
+/// Signature over a [`RegisterPermit`], binding it to the granting owner's key.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Debug, Serialize, Deserialize)]
+pub struct PermitSignature {
+    /// Public key of the signer that granted this permit.
+    pub pub_key: PublicKey,
+    /// Signature produced by `pub_key` over the permit's other fields.
+    pub signature: Signature,
+}
+
+/// Number of entries carried by a [`RegisterExchange`], analogous to
+/// `StorageLevel`, so the receiving node can apply flow control for large
+/// transfers.
+#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct EntriesCount(pub u64);
+
+/// Bulk Register state - its full op-history together with its policy -
+/// packaged for transfer between nodes, e.g. when a section splits or an
+/// adult is promoted/relocated. Analogous to the `DataExchange` used for
+/// chunk replication.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RegisterExchange {
+    /// The Register being transferred, including its full CRDT op-history and policy.
+    pub register: Register,
+    /// Number of entries being shipped, advertised up front for flow control.
+    pub entries_count: EntriesCount,
+}
+
+/// A `RegisterOp` edit together with the parent `EntryHash`es it supersedes.
+///
+/// `RegisterOp` itself carries no notion of what it supersedes, so this
+/// wraps it with the CRDT leaves the edit was generated against, letting a
+/// node reject a stale edit that no longer points at the register's current
+/// concurrent leaves.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RegisterEdit {
+    /// The underlying CRDT edit operation.
+    pub op: RegisterOp<Entry>,
+    /// Hashes of the entries this edit supersedes.
+    pub parents: BTreeSet<EntryHash>,
+}
+
+impl RegisterPermit {
+    /// Returns whether this permit grants access to `address`.
+    pub fn check_address(&self, address: &Address) -> bool {
+        self.allowed_addresses.contains(address)
+    }
+
+    /// Returns whether this permit grants `permission`.
+    pub fn check_permission(&self, permission: &RegisterPermission) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// Returns the bytes of this permit that `permit_signature` is signed over.
+    fn signed_bytes(&self) -> Vec<u8> {
+        bincode::serialize(&(
+            &self.allowed_addresses,
+            &self.permissions,
+            &self.permit_name,
+            &self.chain_id,
+        ))
+        .unwrap_or_default()
+    }
+
+    /// Returns whether `permit_signature` is a valid signature by its `pub_key`
+    /// over this permit's fields, i.e. that the permit wasn't tampered with and
+    /// was genuinely issued by that key.
+    ///
+    /// This does NOT prove `pub_key` is the register's owner — callers must
+    /// separately check `permit_signature.pub_key` against the register's
+    /// actual owner before trusting the grant.
+    pub fn verify_signature(&self) -> bool {
+        self.permit_signature
+            .pub_key
+            .verify(&self.permit_signature.signature, &self.signed_bytes())
+            .is_ok()
+    }
+
+    /// Returns whether this permit is in scope to authorise `permission` on
+    /// `address`, and that its signature is genuine.
+    ///
+    /// The address and the permission must both be explicitly granted, and
+    /// the permit's signature must verify; an empty, unrelated, or forged
+    /// permit must never be treated as authorising access.
+    pub fn authorises(&self, address: &Address, permission: &RegisterPermission) -> bool {
+        self.verify_signature() && self.check_address(address) && self.check_permission(permission)
+    }
+}
+
 /// TODO: docs
 #[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
 pub enum RegisterRead {
     /// Get Register from the network.
-    Get(Address),
+    Get {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// Get a range of entries from an Register object on the network.
     GetRange {
         /// Register address.
@@ -36,22 +148,74 @@ pub enum RegisterRead {
         /// Get first 5 entries:
         /// range: (Index::FromStart(0), Index::FromStart(5))
         range: (Index, Index),
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
     },
     /// Get last entry from the Register.
-    GetLastEntry(Address),
+    GetLastEntry {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
+    /// Get a single entry from the Register by its content-addressed hash.
+    ///
+    /// This lets a client pull one of several concurrent CRDT leaves directly,
+    /// e.g. when resolving a conflict surfaced by `GetEntryHashes`, rather than
+    /// re-reading the whole object.
+    GetEntry {
+        /// Register address.
+        address: Address,
+        /// Hash of the entry to fetch.
+        hash: EntryHash,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
+    /// Get the hashes of the Register's current CRDT leaf entries.
+    GetEntryHashes {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// List current policy
-    GetPublicPolicy(Address),
+    GetPublicPolicy {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// List current policy
-    GetPrivatePolicy(Address),
+    GetPrivatePolicy {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// Get current permissions for a specified user(s).
     GetUserPermissions {
         /// Register address.
         address: Address,
         /// User to get permissions for.
         user: User,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
     },
     /// Get current owner.
-    GetOwner(Address),
+    GetOwner {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
+    /// Get the number of entries currently stored in the Register, without
+    /// fetching the entries themselves, e.g. to decide how to page `GetRange`.
+    GetLength {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
 }
 
 /// TODO: docs
@@ -59,44 +223,91 @@ pub enum RegisterRead {
 #[derive(Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum RegisterWrite {
     /// Create a new Register on the network.
-    New(Register),
+    New {
+        /// The Register to create.
+        register: Register,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// Edit the Register (insert/remove entry).
-    Edit(RegisterOp<Entry>),
+    ///
+    /// The wrapped `RegisterEdit` carries the parent `EntryHash`es that the new
+    /// entry supersedes, so a node can reject an edit that no longer points at
+    /// the register's current concurrent leaves.
+    Edit {
+        /// The edit to apply.
+        edit: RegisterEdit,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
     /// Delete a private Register.
     ///
     /// This operation MUST return an error if applied to public Register. Only the current
     /// owner(s) can perform this action.
-    Delete(Address),
+    Delete {
+        /// Register address.
+        address: Address,
+        /// Optional permit delegating access to this Register on behalf of its owner.
+        permit: Option<RegisterPermit>,
+    },
+    /// Ingest a peer's full Register state - op-history and policy - CRDT-merging
+    /// it into our own copy. Used for node-to-node replication, e.g. on section
+    /// split or when an adult is promoted/relocated.
+    ApplyExchange(RegisterExchange),
 }
 
 impl RegisterRead {
     /// Creates a Response containing an error, with the Response variant corresponding to the
-    /// Request variant.
-    pub fn error(&self, error: Error) -> QueryResponse {
+    /// Request variant, paired with this read's `OperationId` so the client
+    /// layer can correlate it back to the originating request and dedup
+    /// in-flight reads of the same address.
+    pub fn error(&self, error: Error) -> (QueryResponse, OperationId) {
         use RegisterRead::*;
-        match *self {
-            Get(_) => QueryResponse::GetRegister(Err(error)),
+        let response = match *self {
+            Get { .. } => QueryResponse::GetRegister(Err(error)),
             GetRange { .. } => QueryResponse::GetRegisterRange(Err(error)),
-            GetLastEntry(_) => QueryResponse::GetRegisterLastEntry(Err(error)),
-            GetPublicPolicy(_) => QueryResponse::GetRegisterPublicPolicy(Err(error)),
-            GetPrivatePolicy(_) => QueryResponse::GetRegisterPrivatePolicy(Err(error)),
+            GetLastEntry { .. } => QueryResponse::GetRegisterLastEntry(Err(error)),
+            GetEntry { .. } => QueryResponse::GetRegisterEntry(Err(error)),
+            GetEntryHashes { .. } => QueryResponse::GetRegisterEntryHashes(Err(error)),
+            GetPublicPolicy { .. } => QueryResponse::GetRegisterPublicPolicy(Err(error)),
+            GetPrivatePolicy { .. } => QueryResponse::GetRegisterPrivatePolicy(Err(error)),
             GetUserPermissions { .. } => QueryResponse::GetRegisterUserPermissions(Err(error)),
-            GetOwner(_) => QueryResponse::GetRegisterOwner(Err(error)),
-        }
+            GetOwner { .. } => QueryResponse::GetRegisterOwner(Err(error)),
+            GetLength { .. } => QueryResponse::GetRegisterLength(Err(error)),
+        };
+        (response, self.operation_id())
     }
 
     /// Returns the access categorisation of the request.
+    ///
+    /// A delegated [`RegisterPermit`] only resolves the request to a
+    /// public-equivalent grant when its scope actually covers this address and
+    /// a read permission; an out-of-scope, empty, or unrelated permit falls
+    /// back to the caller's own identity being checked against the register's
+    /// own (private) permissions, same as if no permit had been attached.
     pub fn authorisation_kind(&self) -> AuthorisationKind {
         use RegisterRead::*;
-        match *self {
-            Get(address)
-            | GetRange { address, .. }
-            | GetLastEntry(address)
-            | GetPublicPolicy(address)
-            | GetPrivatePolicy(address)
-            | GetUserPermissions { address, .. }
-            | GetOwner(address) => {
-                if address.is_public() {
+        match self {
+            Get { address, permit }
+            | GetRange {
+                address, permit, ..
+            }
+            | GetLastEntry { address, permit }
+            | GetEntry {
+                address, permit, ..
+            }
+            | GetEntryHashes { address, permit }
+            | GetPublicPolicy { address, permit }
+            | GetPrivatePolicy { address, permit }
+            | GetUserPermissions {
+                address, permit, ..
+            }
+            | GetOwner { address, permit }
+            | GetLength { address, permit } => {
+                let permit_grants_read = permit.as_ref().map_or(false, |permit| {
+                    permit.authorises(address, &RegisterPermission::Read)
+                });
+                if permit_grants_read || address.is_public() {
                     AuthorisationKind::Data(DataAuthKind::PublicRead)
                 } else {
                     AuthorisationKind::Data(DataAuthKind::PrivateRead)
@@ -109,15 +320,27 @@ impl RegisterRead {
     pub fn dst_address(&self) -> XorName {
         use RegisterRead::*;
         match self {
-            Get(ref address)
-            | GetRange { ref address, .. }
-            | GetLastEntry(ref address)
-            | GetPublicPolicy(ref address)
-            | GetPrivatePolicy(ref address)
-            | GetUserPermissions { ref address, .. }
-            | GetOwner(ref address) => *address.name(),
+            Get { address, .. }
+            | GetRange { address, .. }
+            | GetLastEntry { address, .. }
+            | GetEntry { address, .. }
+            | GetEntryHashes { address, .. }
+            | GetPublicPolicy { address, .. }
+            | GetPrivatePolicy { address, .. }
+            | GetUserPermissions { address, .. }
+            | GetOwner { address, .. }
+            | GetLength { address, .. } => *address.name(),
         }
     }
+
+    /// Returns the operation identifier for this read, derived from the
+    /// variant and its parameters, which is used to correlate the resulting
+    /// `QueryResponse` to this request and to dedup in-flight reads of the
+    /// same data.
+    pub fn operation_id(&self) -> OperationId {
+        let bytes = bincode::serialize(self).unwrap_or_default();
+        OperationId(XorName::from_content(&bytes))
+    }
 }
 
 impl fmt::Debug for RegisterRead {
@@ -127,13 +350,16 @@ impl fmt::Debug for RegisterRead {
             formatter,
             "RegisterRead::{}",
             match *self {
-                Get(_) => "GetRegister",
+                Get { .. } => "GetRegister",
                 GetRange { .. } => "GetRegisterRange",
-                GetLastEntry(_) => "GetRegisterLastEntry",
+                GetLastEntry { .. } => "GetRegisterLastEntry",
+                GetEntry { .. } => "GetRegisterEntry",
+                GetEntryHashes { .. } => "GetRegisterEntryHashes",
                 GetPublicPolicy { .. } => "GetRegisterPublicPolicy",
                 GetPrivatePolicy { .. } => "GetRegisterPrivatePolicy",
                 GetUserPermissions { .. } => "GetUserPermissions",
                 GetOwner { .. } => "GetOwner",
+                GetLength { .. } => "GetRegisterLength",
             }
         )
     }
@@ -147,24 +373,53 @@ impl RegisterWrite {
     }
 
     /// Returns the access categorisation of the request.
+    ///
+    /// A delegated [`RegisterPermit`] only resolves the write to a
+    /// public-equivalent grant when its scope actually covers this address and
+    /// the `Write` permission; otherwise the write falls back to requiring the
+    /// caller to be the register's own owner, same as if no permit had been
+    /// attached — symmetric to `RegisterRead::authorisation_kind`.
     pub fn authorisation_kind(&self) -> AuthorisationKind {
-        AuthorisationKind::Data(DataAuthKind::Write)
+        use RegisterWrite::*;
+        match self {
+            ApplyExchange(_) => AuthorisationKind::Node(NodeAuthKind::ReplicateData),
+            New { register, permit } => Self::write_authorisation_kind(&register.address(), permit),
+            Edit { edit, permit } => Self::write_authorisation_kind(&edit.op.address, permit),
+            Delete { address, permit } => Self::write_authorisation_kind(address, permit),
+        }
+    }
+
+    /// Resolves the write authorisation kind for `address`, granting the
+    /// public-equivalent kind only when `permit` is in scope for a `Write` on it.
+    fn write_authorisation_kind(
+        address: &Address,
+        permit: &Option<RegisterPermit>,
+    ) -> AuthorisationKind {
+        let permit_grants_write = permit.as_ref().map_or(false, |permit| {
+            permit.authorises(address, &RegisterPermission::Write)
+        });
+        if permit_grants_write {
+            AuthorisationKind::Data(DataAuthKind::PublicWrite)
+        } else {
+            AuthorisationKind::Data(DataAuthKind::Write)
+        }
     }
 
     /// Returns the address of the destination for request.
     pub fn dst_address(&self) -> XorName {
         use RegisterWrite::*;
         match self {
-            New(ref data) => *data.name(),
-            Delete(ref address) => *address.name(),
-            Edit(ref op) => *op.address.name(),
+            New { register, .. } => *register.name(),
+            Delete { address, .. } => *address.name(),
+            Edit { edit, .. } => *edit.op.address.name(),
+            ApplyExchange(exchange) => *exchange.register.name(),
         }
     }
 
     /// Owner of the RegisterWrite
     pub fn owner(&self) -> Option<PublicKey> {
         match self {
-            Self::New(data) => Some(data.owner()),
+            Self::New { register, .. } => Some(register.owner()),
             _ => None,
         }
     }
@@ -177,10 +432,178 @@ impl fmt::Debug for RegisterWrite {
             formatter,
             "RegisterWrite::{}",
             match *self {
-                New(_) => "NewRegister",
-                Delete(_) => "DeleteRegister",
-                Edit(_) => "EditRegister",
+                New { .. } => "NewRegister",
+                Delete { .. } => "DeleteRegister",
+                Edit { .. } => "EditRegister",
+                ApplyExchange(_) => "ApplyRegisterExchange",
             }
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_data_types::Keypair;
+
+    fn address(content: &[u8]) -> Address {
+        Address::Public {
+            name: XorName::from_content(content),
+            tag: 0,
+        }
+    }
+
+    fn private_address(content: &[u8]) -> Address {
+        Address::Private {
+            name: XorName::from_content(content),
+            tag: 0,
+        }
+    }
+
+    fn signed_permit(
+        allowed_addresses: Vec<Address>,
+        permissions: Vec<RegisterPermission>,
+        keypair: &Keypair,
+    ) -> RegisterPermit {
+        let mut permit = RegisterPermit {
+            allowed_addresses,
+            permissions,
+            permit_name: "test-permit".to_string(),
+            chain_id: "test-chain".to_string(),
+            permit_signature: PermitSignature {
+                pub_key: keypair.public_key(),
+                signature: keypair.sign(b""),
+            },
+        };
+        permit.permit_signature.signature = keypair.sign(&permit.signed_bytes());
+        permit
+    }
+
+    #[test]
+    fn authorises_requires_matching_address_and_permission() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = address(b"register");
+        let other_addr = address(b"other-register");
+        let permit = signed_permit(vec![addr], vec![RegisterPermission::Read], &keypair);
+
+        assert!(permit.authorises(&addr, &RegisterPermission::Read));
+        assert!(!permit.authorises(&other_addr, &RegisterPermission::Read));
+        assert!(!permit.authorises(&addr, &RegisterPermission::Write));
+    }
+
+    #[test]
+    fn empty_permit_authorises_nothing() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = address(b"register");
+        let permit = signed_permit(vec![], vec![], &keypair);
+
+        assert!(!permit.authorises(&addr, &RegisterPermission::Read));
+    }
+
+    #[test]
+    fn tampered_permit_fails_signature_verification() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = address(b"register");
+        let mut permit = signed_permit(vec![addr], vec![RegisterPermission::Read], &keypair);
+
+        // Tamper with the permit's scope after it was signed.
+        permit.allowed_addresses.push(address(b"sneaked-in"));
+
+        assert!(!permit.verify_signature());
+        assert!(!permit.authorises(&addr, &RegisterPermission::Read));
+    }
+
+    #[test]
+    fn permit_signed_by_wrong_key_fails_verification() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let attacker_keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = address(b"register");
+        let mut permit = signed_permit(vec![addr], vec![RegisterPermission::Read], &keypair);
+
+        // Swap in an unrelated key as if the attacker claimed the permit was theirs.
+        permit.permit_signature.pub_key = attacker_keypair.public_key();
+
+        assert!(!permit.verify_signature());
+    }
+
+    #[test]
+    fn authorisation_kind_ignores_out_of_scope_permit() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = private_address(b"register");
+        let unrelated_addr = private_address(b"unrelated");
+        let permit = signed_permit(
+            vec![unrelated_addr],
+            vec![RegisterPermission::Read],
+            &keypair,
+        );
+
+        let read = RegisterRead::Get {
+            address: addr,
+            permit: Some(permit),
+        };
+        assert!(matches!(
+            read.authorisation_kind(),
+            AuthorisationKind::Data(DataAuthKind::PrivateRead)
+        ));
+    }
+
+    #[test]
+    fn authorisation_kind_ignores_empty_permit() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = private_address(b"register");
+        let permit = signed_permit(vec![], vec![], &keypair);
+
+        let read = RegisterRead::Get {
+            address: addr,
+            permit: Some(permit),
+        };
+        assert!(matches!(
+            read.authorisation_kind(),
+            AuthorisationKind::Data(DataAuthKind::PrivateRead)
+        ));
+    }
+
+    #[test]
+    fn authorisation_kind_honours_in_scope_permit() {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let addr = private_address(b"register");
+        let permit = signed_permit(vec![addr], vec![RegisterPermission::Read], &keypair);
+
+        let read = RegisterRead::Get {
+            address: addr,
+            permit: Some(permit),
+        };
+        assert!(matches!(
+            read.authorisation_kind(),
+            AuthorisationKind::Data(DataAuthKind::PublicRead)
+        ));
+    }
+
+    #[test]
+    fn operation_id_is_deterministic_for_same_request() {
+        let read = RegisterRead::GetLength {
+            address: address(b"register"),
+            permit: None,
+        };
+        assert_eq!(read.operation_id(), read.operation_id());
+    }
+
+    #[test]
+    fn operation_id_differs_across_variant_and_address() {
+        let read = RegisterRead::GetLength {
+            address: address(b"register"),
+            permit: None,
+        };
+        let other_variant = RegisterRead::GetLastEntry {
+            address: address(b"register"),
+            permit: None,
+        };
+        assert_ne!(read.operation_id(), other_variant.operation_id());
+
+        let other_address = RegisterRead::GetLength {
+            address: address(b"other-register"),
+            permit: None,
+        };
+        assert_ne!(read.operation_id(), other_address.operation_id());
+    }
+}
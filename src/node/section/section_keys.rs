@@ -6,7 +6,29 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use threshold_crypto::{PublicKeySet, SecretKeyShare};
+use std::collections::VecDeque;
+use thiserror::Error;
+use threshold_crypto::{
+    error::Error as BlsError, PublicKey, PublicKeySet, SecretKeyShare, Signature, SignatureShare,
+};
+
+/// Maximum number of retired section key sets kept around so messages signed
+/// just before a key rotation can still be verified during elder churn.
+const MAX_RETIRED_KEY_SETS: usize = 5;
+
+/// Errors returned by `SectionKeysProvider` signing operations.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// There is no current section key share to sign or combine with.
+    #[error("missing current section key share")]
+    MissingSecretKeyShare,
+    /// Combining the given signature shares into a full signature failed.
+    #[error("failed to combine signature shares: {0}")]
+    FailedSignatureShareCombine(#[source] BlsError),
+}
+
+/// Convenience alias for results of `SectionKeysProvider` operations.
+pub type Result<T> = std::result::Result<T, Error>;
 
 /// All the key material needed to sign or combine signature for our section key.
 #[derive(Debug)]
@@ -16,7 +38,22 @@ pub struct SectionKeyShare {
     /// Index of the owner of this key share within the set of all section elders.
     pub index: usize,
     /// Secret Key share.
-    pub secret_key_share: SecretKeyShare,
+    secret_key_share: SecretKeyShare,
+}
+
+impl SectionKeyShare {
+    /// Creates a new `SectionKeyShare` from the given key-generation output.
+    pub fn new(
+        public_key_set: PublicKeySet,
+        index: usize,
+        secret_key_share: SecretKeyShare,
+    ) -> Self {
+        Self {
+            public_key_set,
+            index,
+            secret_key_share,
+        }
+    }
 }
 
 /// Struct that holds the current section keys and helps with new key generation.
@@ -26,4 +63,190 @@ pub struct SectionKeysProvider {
     current: Option<SectionKeyShare>,
     /// The new keys to use when section update completes.
     pending: Option<SectionKeyShare>,
+    /// Bounded cache of public key sets retired during section key rotation,
+    /// kept so a message signed by the immediately-prior key can still be verified.
+    /// When full, the earliest-retired set is evicted first; lookups do not
+    /// change the eviction order.
+    retired: VecDeque<PublicKeySet>,
+}
+
+impl SectionKeysProvider {
+    /// Creates a new provider, optionally seeded with the current key share.
+    pub fn new(current: Option<SectionKeyShare>) -> Self {
+        Self {
+            current,
+            pending: None,
+            retired: VecDeque::new(),
+        }
+    }
+
+    /// Sets the key share to use once the section update completes.
+    pub fn insert_pending(&mut self, share: SectionKeyShare) {
+        self.pending = Some(share);
+    }
+
+    /// Promotes `pending` to `current`, retiring the previous current key set
+    /// into the bounded historical cache instead of dropping it.
+    pub fn finalise_pending(&mut self) {
+        let new_current = match self.pending.take() {
+            Some(share) => share,
+            None => return,
+        };
+
+        if let Some(old_current) = self.current.replace(new_current) {
+            if self.retired.len() == MAX_RETIRED_KEY_SETS {
+                let _ = self.retired.pop_front();
+            }
+            self.retired.push_back(old_current.public_key_set);
+        }
+    }
+
+    /// Returns the key share matching `public_key`, if it is our current or
+    /// pending key share. Retired key sets have no associated secret share,
+    /// so they are only usable via `known_key` for verification.
+    pub fn key_share_for(&self, public_key: &PublicKey) -> Option<&SectionKeyShare> {
+        self.current
+            .as_ref()
+            .filter(|share| &share.public_key_set.public_key() == public_key)
+            .or_else(|| {
+                self.pending
+                    .as_ref()
+                    .filter(|share| &share.public_key_set.public_key() == public_key)
+            })
+    }
+
+    /// Returns whether `key` is our current, pending, or a recently-retired
+    /// section public key, for use when verifying a signature against it.
+    pub fn known_key(&self, key: &PublicKey) -> bool {
+        self.current
+            .as_ref()
+            .map_or(false, |share| &share.public_key_set.public_key() == key)
+            || self
+                .pending
+                .as_ref()
+                .map_or(false, |share| &share.public_key_set.public_key() == key)
+            || self
+                .retired
+                .iter()
+                .any(|public_key_set| &public_key_set.public_key() == key)
+    }
+
+    /// Signs `data` with our current section key share, without exposing the
+    /// underlying `SecretKeyShare`. Returns the signer's index within the
+    /// current key set alongside the resulting signature share, so callers
+    /// can build up a threshold signature.
+    pub fn sign(&self, data: &[u8]) -> Result<(usize, SignatureShare)> {
+        let share = self.current.as_ref().ok_or(Error::MissingSecretKeyShare)?;
+        Ok((share.index, share.secret_key_share.sign(data)))
+    }
+
+    /// Combines the given index-tagged signature shares into a full
+    /// `Signature` using our current public key set. The combined signature
+    /// is not verified here.
+    pub fn combine_signatures<'a>(
+        &self,
+        shares: impl IntoIterator<Item = (usize, &'a SignatureShare)>,
+    ) -> Result<Signature> {
+        let share = self.current.as_ref().ok_or(Error::MissingSecretKeyShare)?;
+        share
+            .public_key_set
+            .combine_signatures(shares)
+            .map_err(Error::FailedSignatureShareCombine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use threshold_crypto::SecretKeySet;
+
+    fn gen_share() -> SectionKeyShare {
+        let sk_set = SecretKeySet::random(0, &mut rand::thread_rng());
+        let public_key_set = sk_set.public_keys();
+        let secret_key_share = sk_set.secret_key_share(0);
+        SectionKeyShare::new(public_key_set, 0, secret_key_share)
+    }
+
+    #[test]
+    fn retired_cache_evicts_oldest_beyond_capacity() {
+        let mut provider = SectionKeysProvider::new(Some(gen_share()));
+        let mut retired_keys = Vec::new();
+
+        for _ in 0..MAX_RETIRED_KEY_SETS + 2 {
+            let current_key = provider
+                .current
+                .as_ref()
+                .expect("current key share should be set")
+                .public_key_set
+                .public_key();
+            retired_keys.push(current_key);
+            provider.insert_pending(gen_share());
+            provider.finalise_pending();
+        }
+
+        assert_eq!(provider.retired.len(), MAX_RETIRED_KEY_SETS);
+
+        let evicted = &retired_keys[..retired_keys.len() - MAX_RETIRED_KEY_SETS];
+        for key in evicted {
+            assert!(!provider.known_key(key));
+        }
+
+        let kept = &retired_keys[retired_keys.len() - MAX_RETIRED_KEY_SETS..];
+        for key in kept {
+            assert!(provider.known_key(key));
+        }
+    }
+
+    #[test]
+    fn key_share_for_finds_current_and_pending_but_not_retired() {
+        let first = gen_share();
+        let first_key = first.public_key_set.public_key();
+        let mut provider = SectionKeysProvider::new(Some(first));
+
+        let pending = gen_share();
+        let pending_key = pending.public_key_set.public_key();
+        provider.insert_pending(pending);
+
+        assert!(provider.key_share_for(&first_key).is_some());
+        assert!(provider.key_share_for(&pending_key).is_some());
+        assert!(provider.known_key(&first_key));
+        assert!(provider.known_key(&pending_key));
+
+        provider.finalise_pending();
+
+        // `first_key` is now retired: known for verification, but with no
+        // secret share available to sign or combine with.
+        assert!(provider.known_key(&first_key));
+        assert!(provider.key_share_for(&first_key).is_none());
+        assert!(provider.key_share_for(&pending_key).is_some());
+    }
+
+    #[test]
+    fn sign_and_combine_signatures_roundtrip() {
+        let sk_set = SecretKeySet::random(0, &mut rand::thread_rng());
+        let public_key_set = sk_set.public_keys();
+        let secret_key_share = sk_set.secret_key_share(0);
+        let share = SectionKeyShare::new(public_key_set.clone(), 0, secret_key_share);
+        let provider = SectionKeysProvider::new(Some(share));
+
+        let data = b"hello section";
+        let (index, sig_share) = provider
+            .sign(data)
+            .expect("should sign with current key share");
+        assert_eq!(index, 0);
+
+        let signature = provider
+            .combine_signatures(vec![(index, &sig_share)])
+            .expect("should combine signature shares");
+        assert!(public_key_set.public_key().verify(&signature, data));
+    }
+
+    #[test]
+    fn sign_fails_without_current_key_share() {
+        let provider = SectionKeysProvider::new(None);
+        assert!(matches!(
+            provider.sign(b"data"),
+            Err(Error::MissingSecretKeyShare)
+        ));
+    }
 }